@@ -1,9 +1,18 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 //! CBOR decoder
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+#[cfg(feature = "std")]
 use std::{
-    fmt,
+    collections::BTreeMap,
     io::{self, Read, Seek, SeekFrom},
-    result,
 };
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+use core::fmt;
 
 /// Type alias for a blake3 hash
 pub type Hash = [u8; 32];
@@ -29,10 +38,38 @@ pub enum ParseError {
     InvalidHashAlgorithm,
     /// Invalid hash length (not 32)
     InvalidHashLength,
+    /// A map key was present but not a text string
+    InvalidMapKey,
+    /// A text string was not valid utf8
+    InvalidUtf8,
+    /// An indefinite-length array, map, byte string or text string was found
+    /// while validating with [`Strictness::Strict`]
+    IndefiniteLength,
+    /// An integer or length was not encoded in its shortest form while
+    /// validating with [`Strictness::Strict`]
+    NonMinimalInt,
+    /// A map key was not a string, or map keys were not in strictly
+    /// increasing canonical order, while validating with [`Strictness::Strict`]
+    NonCanonicalMapKey,
+    /// A 16 or 32 bit float was found while validating with
+    /// [`Strictness::Strict`] (dag-cbor only permits 64 bit floats)
+    NonCanonicalFloat,
+    /// Nesting depth exceeded `Limits::max_depth`
+    DepthExceeded,
+    /// Number of links found exceeded `Limits::max_links`
+    TooManyLinks,
     /// Generic io error
+    #[cfg(feature = "std")]
     IoError(io::Error),
+    /// A reader ran out of data. Present only in the `no_std` build, in place
+    /// of [`ParseError::IoError`], for use by custom [`Reader`] impls; this
+    /// crate's own [`ByteCursor`] reports running out of data as
+    /// [`ParseError::UnexpectedEof`] instead.
+    #[cfg(not(feature = "std"))]
+    NotEnoughData,
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for ParseError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
@@ -48,6 +85,7 @@ impl fmt::Display for ParseError {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<io::Error> for ParseError {
     fn from(e: io::Error) -> Self {
         match e.kind() {
@@ -57,29 +95,179 @@ impl From<io::Error> for ParseError {
     }
 }
 
+/// An owned, decoded dag-cbor value.
+///
+/// Produced by [`decode`], which materializes an entire block instead of
+/// just extracting its links.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ipld {
+    /// The `null` value
+    Null,
+    /// A boolean
+    Bool(bool),
+    /// An integer, signed or unsigned
+    Integer(i128),
+    /// A 64 bit float
+    Float(f64),
+    /// A byte string
+    Bytes(Vec<u8>),
+    /// A text string
+    String(String),
+    /// A list of values
+    List(Vec<Ipld>),
+    /// A map of string keys to values
+    Map(BTreeMap<String, Ipld>),
+    /// A link to another block, as a (codec, hash) pair
+    Link(u64, Hash),
+}
+
+/// Controls how strictly a block is checked against the DAG-CBOR spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    /// Accept any construct this crate knows how to parse, as `references`
+    /// always has. This may accept blocks that are not canonical DAG-CBOR,
+    /// and therefore do not hash to their claimed CID.
+    Lenient,
+    /// Reject anything that is not canonical DAG-CBOR: indefinite-length
+    /// arrays/maps/strings, non-minimal integer encodings, 16/32 bit floats,
+    /// non-string or non-canonically-ordered map keys, and tags other than 42.
+    Strict,
+}
+
+/// Bounds on the work [`references`]/[`references_strict`] will do for a
+/// single block, so that a hostile block can't blow the stack or the
+/// output buffer before being rejected.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Maximum array/map nesting depth.
+    pub max_depth: usize,
+    /// Maximum number of links to collect before aborting.
+    pub max_links: usize,
+}
+
+impl Limits {
+    /// No limits at all, matching this crate's previous unbounded behavior.
+    pub const UNLIMITED: Limits = Limits {
+        max_depth: usize::MAX,
+        max_links: usize::MAX,
+    };
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::UNLIMITED
+    }
+}
+
+/// A minimal reader abstraction the parser is built on top of, so that it
+/// can run without `std::io` in `no_std`/embedded and wasm-minimal contexts.
+///
+/// Implemented for any `R: std::io::Read + std::io::Seek` behind the default
+/// `std` feature, and unconditionally for [`ByteCursor`].
+pub trait Reader {
+    /// Reads exactly `buf.len()` bytes, filling `buf`, or fails if the
+    /// underlying source runs out first.
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ParseError>;
+    /// Moves the read position `offset` bytes relative to the current
+    /// position. `offset` may be negative to seek backwards.
+    fn seek_relative(&mut self, offset: i64) -> Result<(), ParseError>;
+}
+
+#[cfg(feature = "std")]
+impl<R: Read + Seek> Reader for R {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ParseError> {
+        Read::read_exact(self, buf).map_err(ParseError::from)
+    }
+
+    fn seek_relative(&mut self, offset: i64) -> Result<(), ParseError> {
+        self.seek(SeekFrom::Current(offset))?;
+        Ok(())
+    }
+}
+
+/// A `no_std`-friendly [`Reader`] over an in-memory byte slice: tracks a
+/// read offset into `data` and implements seeking by adjusting that offset
+/// directly, with no actual I/O.
+#[derive(Debug, Clone, Copy)]
+pub struct ByteCursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteCursor<'a> {
+    /// Wraps `data` for reading, starting at offset 0.
+    pub fn new(data: &'a [u8]) -> Self {
+        ByteCursor { data, pos: 0 }
+    }
+}
+
+impl<'a> Reader for ByteCursor<'a> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ParseError> {
+        let end = self
+            .pos
+            .checked_add(buf.len())
+            .ok_or(ParseError::UnexpectedEof)?;
+        let src = self.data.get(self.pos..end).ok_or(ParseError::UnexpectedEof)?;
+        buf.copy_from_slice(src);
+        self.pos = end;
+        Ok(())
+    }
+
+    fn seek_relative(&mut self, offset: i64) -> Result<(), ParseError> {
+        let pos = self.pos as i64 + offset;
+        let pos = usize::try_from(pos).map_err(|_| ParseError::UnexpectedEof)?;
+        if pos > self.data.len() {
+            return Err(ParseError::UnexpectedEof);
+        }
+        self.pos = pos;
+        Ok(())
+    }
+}
+
+/// A [`Reader`] that can additionally hand out a direct, zero-copy reference
+/// into its underlying buffer instead of copying bytes into an owned `Vec`
+/// like [`read_bytes`] does.
+///
+/// Only implemented by [`ByteCursor`]: a streaming `std::io::Read` source
+/// has no buffer to borrow from.
+pub trait SliceReader: Reader {
+    /// Returns the next `len` bytes without copying them, advancing the
+    /// read position as [`Reader::read_exact`] would.
+    fn read_slice(&mut self, len: usize) -> Result<&[u8], ParseError>;
+}
+
+impl<'a> SliceReader for ByteCursor<'a> {
+    fn read_slice(&mut self, len: usize) -> Result<&[u8], ParseError> {
+        let end = self.pos.checked_add(len).ok_or(ParseError::UnexpectedEof)?;
+        let slice = self.data.get(self.pos..end).ok_or(ParseError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(slice)
+    }
+}
+
 /// Reads a u8 from a byte stream.
-fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+fn read_u8<R: Reader>(r: &mut R) -> Result<u8, ParseError> {
     let mut buf = [0; 1];
     r.read_exact(&mut buf)?;
     Ok(buf[0])
 }
 
 /// Reads a u16 from a byte stream.
-fn read_u16<R: Read>(r: &mut R) -> io::Result<u16> {
+fn read_u16<R: Reader>(r: &mut R) -> Result<u16, ParseError> {
     let mut buf = [0; 2];
     r.read_exact(&mut buf)?;
     Ok(u16::from_be_bytes(buf))
 }
 
 /// Reads a u32 from a byte stream.
-fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+fn read_u32<R: Reader>(r: &mut R) -> Result<u32, ParseError> {
     let mut buf = [0; 4];
     r.read_exact(&mut buf)?;
     Ok(u32::from_be_bytes(buf))
 }
 
 /// Reads a u64 from a byte stream.
-fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+fn read_u64<R: Reader>(r: &mut R) -> Result<u64, ParseError> {
     let mut buf = [0; 8];
     r.read_exact(&mut buf)?;
     Ok(u64::from_be_bytes(buf))
@@ -90,7 +278,7 @@ fn parse_u64_varint(mut input: &[u8]) -> Result<(u64, &[u8]), ParseError> {
     let mut shift: u32 = 0;
 
     loop {
-        let byte = input[0];
+        let byte = *input.first().ok_or(ParseError::UnexpectedEof)?;
         input = &input[1..];
 
         let bits = (byte & 0x7F) as u64;
@@ -110,18 +298,82 @@ fn parse_u64_varint(mut input: &[u8]) -> Result<(u64, &[u8]), ParseError> {
 }
 
 /// Reads `len` number of bytes from a byte stream.
-fn read_bytes<R: Read>(r: &mut R, len: usize) -> result::Result<Vec<u8>, ParseError> {
-    // Limit up-front allocations to 16KiB as the length is user controlled.
-    let mut buf = Vec::with_capacity(len.min(16 * 1024));
-    r.take(len as u64).read_to_end(&mut buf)?;
-    if buf.len() != len {
-        return Err(ParseError::UnexpectedEof);
+///
+/// Reads in fixed-size chunks rather than allocating `len` bytes up front,
+/// since `len` is user controlled.
+fn read_bytes<R: Reader>(r: &mut R, len: usize) -> Result<Vec<u8>, ParseError> {
+    const CHUNK: usize = 16 * 1024;
+    let mut buf = Vec::with_capacity(len.min(CHUNK));
+    let mut remaining = len;
+    while remaining > 0 {
+        let take = remaining.min(CHUNK);
+        let start = buf.len();
+        buf.resize(start + take, 0);
+        r.read_exact(&mut buf[start..])?;
+        remaining -= take;
     }
     Ok(buf)
 }
 
+/// A parsed CIDv1 with a generic, multihash-aware digest.
+///
+/// Produced by [`references_generic`] for links that may use any multihash,
+/// as opposed to [`references`], which only accepts blake3-256 and returns
+/// the bare `(codec, digest)` pair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cid {
+    /// The CID version. Always 1: CIDv0 links are not valid dag-cbor.
+    pub version: u64,
+    /// The content codec, e.g. `0x71` for dag-cbor.
+    pub codec: u64,
+    /// The multihash function code, e.g. `0x1e` for blake3-256.
+    pub hash_code: u64,
+    /// The raw digest bytes.
+    pub digest: Vec<u8>,
+}
+
+/// Splits `bytes` at `at`, bounds-checked instead of panicking like
+/// `<[u8]>::split_at` does when `at > bytes.len()`.
+fn split_at_checked(bytes: &[u8], at: usize) -> Result<(&[u8], &[u8]), ParseError> {
+    if bytes.len() < at {
+        return Err(ParseError::LengthOutOfRange);
+    }
+    Ok(bytes.split_at(at))
+}
+
+/// Parses the CID prefix shared by every link reader: the multibase
+/// identity byte, the version header (must be `[0, 1]` per
+/// https://github.com/ipld/specs/blob/master/block-layer/codecs/dag-cbor.md#links),
+/// and the multicodec varint. Returns the codec and the remaining multihash
+/// bytes. Shared by [`read_link`], [`read_cid`] and [`read_link_slice`] so
+/// their header checks can't drift apart.
+fn parse_cid_prefix(bytes: &[u8]) -> Result<(u64, &[u8]), ParseError> {
+    if bytes[0] != 0 {
+        return Err(ParseError::InvalidCidPrefix(bytes[0]));
+    }
+    let (version_header, rest) = split_at_checked(bytes, 2)?;
+    if version_header != [0, 1] {
+        return Err(ParseError::InvalidCidVersion);
+    }
+    parse_u64_varint(rest)
+}
+
+/// Parses a blake3-256 multihash (hash code `0x1e`, digest length 32) out of
+/// the multihash bytes returned by [`parse_cid_prefix`]. Shared by
+/// [`read_link`] and [`read_link_slice`], which both require blake3.
+fn parse_blake3_digest(rest: &[u8]) -> Result<&[u8], ParseError> {
+    let (mh_header, rest) = split_at_checked(rest, 2)?;
+    if mh_header != [0x1e, 0x20] {
+        return Err(ParseError::InvalidHashAlgorithm);
+    }
+    if rest.len() != 32 {
+        return Err(ParseError::InvalidHashLength);
+    }
+    Ok(rest)
+}
+
 /// Reads a cid from a stream of cbor encoded bytes.
-fn read_link<R: Read>(r: &mut R) -> Result<(u64, Hash), ParseError> {
+fn read_link<R: Reader>(r: &mut R) -> Result<(u64, Hash), ParseError> {
     let ty = read_u8(r)?;
     if ty != 0x58 {
         return Err(ParseError::UnknownTag(ty));
@@ -131,42 +383,101 @@ fn read_link<R: Read>(r: &mut R) -> Result<(u64, Hash), ParseError> {
         return Err(ParseError::LengthOutOfRange);
     }
     let bytes = read_bytes(r, len as usize)?;
-    if bytes[0] != 0 {
-        return Err(ParseError::InvalidCidPrefix(bytes[0]));
+    if bytes.len() < 32 {
+        return Err(ParseError::LengthOutOfRange);
     }
+    let (codec, rest) = parse_cid_prefix(&bytes)?;
+    let digest = parse_blake3_digest(rest)?;
+    Ok((codec, <[u8; 32]>::try_from(digest).unwrap()))
+}
 
-    if bytes.len() < 32 {
+/// Reads a cid from a stream of cbor encoded bytes, accepting any multihash
+/// instead of requiring blake3-256 like [`read_link`] does.
+fn read_cid<R: Reader>(r: &mut R) -> Result<Cid, ParseError> {
+    let ty = read_u8(r)?;
+    if ty != 0x58 {
+        return Err(ParseError::UnknownTag(ty));
+    }
+    let len = read_u8(r)?;
+    if len == 0 {
         return Err(ParseError::LengthOutOfRange);
     }
-    // check that version is 1
-    // skip the first byte per
-    // https://github.com/ipld/specs/blob/master/block-layer/codecs/dag-cbor.md#links
-    let (version_header, rest) = bytes.split_at(2);
-    if version_header != [0, 1] {
-        return Err(ParseError::InvalidCidVersion);
+    let bytes = read_bytes(r, len as usize)?;
+    if bytes.len() < 2 {
+        return Err(ParseError::LengthOutOfRange);
     }
-    let (codec, rest) = parse_u64_varint(rest)?;
-    // check that hash code is 0x1e (blake3) and length is 32
-    let (mh_header, rest) = rest.split_at(2);
-    if mh_header != [0x1e, 0x20] {
-        return Err(ParseError::InvalidHashAlgorithm);
+    let (codec, rest) = parse_cid_prefix(&bytes)?;
+    let (hash_code, rest) = parse_u64_varint(rest)?;
+    let (digest_len, rest) = parse_u64_varint(rest)?;
+    if rest.len() as u64 != digest_len {
+        return Err(ParseError::LengthOutOfRange);
     }
-    if rest.len() != 32 {
-        return Err(ParseError::InvalidHashLength);
+    Ok(Cid {
+        version: 1,
+        codec,
+        hash_code,
+        digest: rest.to_vec(),
+    })
+}
+
+/// Zero-copy variant of [`read_link`]: reads the CID bytes directly out of
+/// `r`'s underlying buffer via [`SliceReader::read_slice`] instead of
+/// allocating a `Vec` through [`read_bytes`], copying only the final 32-byte
+/// digest into the returned [`Hash`].
+fn read_link_slice<R: SliceReader>(r: &mut R) -> Result<(u64, Hash), ParseError> {
+    let ty = read_u8(r)?;
+    if ty != 0x58 {
+        return Err(ParseError::UnknownTag(ty));
     }
-    let bytes = <[u8; 32]>::try_from(rest).unwrap();
-    Ok((codec, bytes))
+    let len = read_u8(r)?;
+    if len == 0 {
+        return Err(ParseError::LengthOutOfRange);
+    }
+    let bytes = r.read_slice(len as usize)?;
+    if bytes.len() < 32 {
+        return Err(ParseError::LengthOutOfRange);
+    }
+    let (codec, rest) = parse_cid_prefix(bytes)?;
+    let digest = parse_blake3_digest(rest)?;
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(digest);
+    Ok((codec, hash))
 }
 
 /// Reads the len given a base.
-fn read_len<R: Read + Seek>(r: &mut R, major: u8) -> Result<usize, ParseError> {
+///
+/// Also used to read the value of a major type 0 (unsigned integer) or
+/// major type 1 (negative integer) item, by passing the low 5 bits of its
+/// header byte as `major` — the header layout is identical.
+fn read_len<R: Reader>(r: &mut R, major: u8, strictness: Strictness) -> Result<usize, ParseError> {
     Ok(match major {
         0x00..=0x17 => major as usize,
-        0x18 => read_u8(r)? as usize,
-        0x19 => read_u16(r)? as usize,
-        0x1a => read_u32(r)? as usize,
+        0x18 => {
+            let len = read_u8(r)? as usize;
+            if strictness == Strictness::Strict && len < 24 {
+                return Err(ParseError::NonMinimalInt);
+            }
+            len
+        }
+        0x19 => {
+            let len = read_u16(r)? as usize;
+            if strictness == Strictness::Strict && len < 256 {
+                return Err(ParseError::NonMinimalInt);
+            }
+            len
+        }
+        0x1a => {
+            let len = read_u32(r)? as usize;
+            if strictness == Strictness::Strict && len < 65536 {
+                return Err(ParseError::NonMinimalInt);
+            }
+            len
+        }
         0x1b => {
             let len = read_u64(r)?;
+            if strictness == Strictness::Strict && len < (1 << 32) {
+                return Err(ParseError::NonMinimalInt);
+            }
             if len > usize::max_value() as u64 {
                 return Err(ParseError::LengthOutOfRange);
             }
@@ -176,135 +487,665 @@ fn read_len<R: Read + Seek>(r: &mut R, major: u8) -> Result<usize, ParseError> {
     })
 }
 
-/// Read a dag-cbor block and extract all the links.
-///
-/// 'r' is a reader that is expected to be at the start of a dag-cbor block.
-/// 'res' is a vector that will be populated with all the links found.
-///
-/// Will fail unless all links are blake3 hashes.
-pub fn references<R: Read + Seek>(r: &mut R, res: &mut Vec<(u64, Hash)>) -> Result<(), ParseError> {
-    let major = read_u8(r)?;
-    match major {
-        // Major type 0: an unsigned integer
-        0x00..=0x17 => {}
-        0x18 => {
-            r.seek(SeekFrom::Current(1))?;
+/// Compares two canonical dag-cbor map keys: shortest first, then
+/// lexicographic, per the [dag-cbor spec](https://github.com/ipld/specs/blob/master/block-layer/codecs/dag-cbor.md#strictness).
+fn canonical_key_order(a: &[u8], b: &[u8]) -> core::cmp::Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// A still-open array or map, recording how many more items it needs before
+/// the traversal can pop it off the work stack.
+enum Frame {
+    /// `remaining` more array elements to read.
+    Array { remaining: usize },
+    /// `remaining` more key/value slots to read (2 per pair). Even means the
+    /// next slot is a key, odd means it's a value.
+    Map {
+        remaining: usize,
+        prev_key: Option<Vec<u8>>,
+    },
+    /// An indefinite-length array, terminated by a break byte rather than a count.
+    IndefiniteArray,
+    /// An indefinite-length map, terminated by a break byte rather than a count.
+    IndefiniteMap { expecting_key: bool },
+}
+
+/// Marks the slot at the top of `stack` (if any) as filled in by the item
+/// that was just read, popping and cascading into the parent frame(s) as
+/// they complete.
+fn finish_item(stack: &mut Vec<Frame>) {
+    loop {
+        match stack.last_mut() {
+            Some(Frame::Array { remaining }) => {
+                *remaining -= 1;
+                if *remaining == 0 {
+                    stack.pop();
+                    continue;
+                }
+            }
+            Some(Frame::Map { remaining, .. }) => {
+                *remaining -= 1;
+                if *remaining == 0 {
+                    stack.pop();
+                    continue;
+                }
+            }
+            Some(Frame::IndefiniteMap { expecting_key }) => {
+                *expecting_key = !*expecting_key;
+            }
+            Some(Frame::IndefiniteArray) | None => {}
         }
-        0x19 => {
-            r.seek(SeekFrom::Current(2))?;
+        break;
+    }
+}
+
+/// Pushes a definite-length container frame, unless it is already empty (in
+/// which case there is nothing to push: the caller should treat this as an
+/// already-finished item). Enforces `limits.max_depth`.
+fn push_frame(stack: &mut Vec<Frame>, frame: Frame, limits: &Limits) -> Result<bool, ParseError> {
+    let empty = matches!(
+        &frame,
+        Frame::Array { remaining: 0 } | Frame::Map { remaining: 0, .. }
+    );
+    if empty {
+        return Ok(false);
+    }
+    if stack.len() >= limits.max_depth {
+        return Err(ParseError::DepthExceeded);
+    }
+    stack.push(frame);
+    Ok(true)
+}
+
+/// Reads exactly one dag-cbor value at the current position (transparently
+/// unwrapping any non-42 tags in front of it). Returns `true` if the value
+/// was an array or map whose frame is now on top of `stack` awaiting its
+/// children, or `false` if it was fully consumed and the caller should call
+/// [`finish_item`].
+fn read_item<R: Reader, L>(
+    r: &mut R,
+    res: &mut Vec<L>,
+    strictness: Strictness,
+    limits: &Limits,
+    stack: &mut Vec<Frame>,
+    read_link: &mut impl FnMut(&mut R) -> Result<L, ParseError>,
+) -> Result<bool, ParseError> {
+    let expecting_key = match stack.last() {
+        Some(Frame::Map { remaining, .. }) => remaining % 2 == 0,
+        Some(Frame::IndefiniteMap { expecting_key }) => *expecting_key,
+        _ => false,
+    };
+
+    // Canonical map keys must be plain (non-tagged) text strings, checked
+    // directly against the previous key instead of going through the
+    // generic dispatch below.
+    if strictness == Strictness::Strict && expecting_key {
+        let key_major = read_u8(r)?;
+        if !(0x60..=0x7b).contains(&key_major) {
+            return Err(ParseError::NonCanonicalMapKey);
         }
-        0x1a => {
-            r.seek(SeekFrom::Current(4))?;
+        let key_len = read_len(r, key_major - 0x60, strictness)?;
+        let key = read_bytes(r, key_len)?;
+        if let Some(Frame::Map { prev_key: Some(prev), .. }) = stack.last() {
+            if canonical_key_order(&key, prev) != core::cmp::Ordering::Greater {
+                return Err(ParseError::NonCanonicalMapKey);
+            }
         }
-        0x1b => {
-            r.seek(SeekFrom::Current(8))?;
+        if let Some(Frame::Map { prev_key, .. }) = stack.last_mut() {
+            *prev_key = Some(key);
         }
+        return Ok(false);
+    }
 
-        // Major type 1: a negative integer
-        0x20..=0x37 => {}
-        0x38 => {
-            r.seek(SeekFrom::Current(1))?;
-        }
-        0x39 => {
-            r.seek(SeekFrom::Current(2))?;
+    loop {
+        let major = read_u8(r)?;
+        return Ok(match major {
+            // Major type 0: an unsigned integer
+            0x00..=0x17 => false,
+            0x18..=0x1b => {
+                read_len(r, major, strictness)?;
+                false
+            }
+
+            // Major type 1: a negative integer
+            0x20..=0x37 => false,
+            0x38..=0x3b => {
+                read_len(r, major - 0x20, strictness)?;
+                false
+            }
+
+            // Major type 2: a byte string
+            0x40..=0x5b => {
+                let len = read_len(r, major - 0x40, strictness)?;
+                r.seek_relative(len as i64)?;
+                false
+            }
+
+            // Major type 3: a text string
+            0x60..=0x7b => {
+                let len = read_len(r, major - 0x60, strictness)?;
+                r.seek_relative(len as i64)?;
+                false
+            }
+
+            // Major type 2/3: indefinite-length byte/text string
+            0x5f | 0x7f if strictness == Strictness::Strict => {
+                return Err(ParseError::IndefiniteLength);
+            }
+
+            // Major type 4: an array of data items
+            0x80..=0x9b => {
+                let len = read_len(r, major - 0x80, strictness)?;
+                push_frame(stack, Frame::Array { remaining: len }, limits)?
+            }
+
+            // Major type 4: an array of data items (indefinite length)
+            0x9f => {
+                if strictness == Strictness::Strict {
+                    return Err(ParseError::IndefiniteLength);
+                }
+                if stack.len() >= limits.max_depth {
+                    return Err(ParseError::DepthExceeded);
+                }
+                stack.push(Frame::IndefiniteArray);
+                true
+            }
+
+            // Major type 5: a map of pairs of data items
+            0xa0..=0xbb => {
+                let len = read_len(r, major - 0xa0, strictness)?;
+                push_frame(
+                    stack,
+                    Frame::Map {
+                        remaining: len.checked_mul(2).ok_or(ParseError::LengthOutOfRange)?,
+                        prev_key: None,
+                    },
+                    limits,
+                )?
+            }
+
+            // Major type 5: a map of pairs of data items (indefinite length)
+            0xbf => {
+                if strictness == Strictness::Strict {
+                    return Err(ParseError::IndefiniteLength);
+                }
+                if stack.len() >= limits.max_depth {
+                    return Err(ParseError::DepthExceeded);
+                }
+                stack.push(Frame::IndefiniteMap { expecting_key: true });
+                true
+            }
+
+            // Major type 6: optional semantic tagging of other major types
+            0xd8 => {
+                let tag = read_u8(r)?;
+                if tag == 42 {
+                    if res.len() >= limits.max_links {
+                        return Err(ParseError::TooManyLinks);
+                    }
+                    res.push(read_link(r)?);
+                    false
+                } else if strictness == Strictness::Strict {
+                    return Err(ParseError::UnknownTag(tag));
+                } else {
+                    // the tag is transparent: loop around to read the value it wraps
+                    continue;
+                }
+            }
+
+            // Major type 7: floating-point numbers and other simple data types that need no content
+            0xf4..=0xf7 => false,
+            0xf8 => {
+                r.seek_relative(1)?;
+                false
+            }
+            0xf9 | 0xfa if strictness == Strictness::Strict => {
+                return Err(ParseError::NonCanonicalFloat);
+            }
+            0xf9 => {
+                r.seek_relative(2)?;
+                false
+            }
+            0xfa => {
+                r.seek_relative(4)?;
+                false
+            }
+            0xfb => {
+                r.seek_relative(8)?;
+                false
+            }
+            major => return Err(ParseError::UnexpectedCode(major)),
+        });
+    }
+}
+
+/// Shared iterative traversal behind [`references`] and [`references_strict`].
+///
+/// Uses an explicit work stack of open array/map frames instead of
+/// recursion, so a block nested thousands of levels deep cannot blow the
+/// stack; `limits` bounds both the nesting depth and the number of links
+/// collected.
+fn references_impl<R: Reader, L>(
+    r: &mut R,
+    res: &mut Vec<L>,
+    strictness: Strictness,
+    limits: Limits,
+    mut read_link: impl FnMut(&mut R) -> Result<L, ParseError>,
+) -> Result<(), ParseError> {
+    let mut stack: Vec<Frame> = Vec::new();
+    loop {
+        if let Some(frame) = stack.last() {
+            if matches!(frame, Frame::IndefiniteArray | Frame::IndefiniteMap { .. }) {
+                let b = read_u8(r)?;
+                if b == 0xff {
+                    stack.pop();
+                    finish_item(&mut stack);
+                    if stack.is_empty() {
+                        break;
+                    }
+                    continue;
+                }
+                r.seek_relative(-1)?;
+            }
         }
-        0x3a => {
-            r.seek(SeekFrom::Current(4))?;
+        let pushed = read_item(r, res, strictness, &limits, &mut stack, &mut read_link)?;
+        if !pushed {
+            finish_item(&mut stack);
         }
-        0x3b => {
-            r.seek(SeekFrom::Current(8))?;
+        if stack.is_empty() {
+            break;
         }
+    }
+    Ok(())
+}
+
+/// Read a dag-cbor block and extract all the links.
+///
+/// 'r' is a reader that is expected to be at the start of a dag-cbor block.
+/// 'res' is a vector that will be populated with all the links found.
+/// 'limits' bounds the nesting depth and number of links, guarding against
+/// adversarial blocks; pass [`Limits::UNLIMITED`] to restore the crate's
+/// previous unbounded behavior.
+///
+/// Will fail unless all links are blake3 hashes; use [`references_generic`]
+/// to also accept links hashed with other multihashes. Accepts any
+/// construct this crate knows how to parse; use [`references_strict`] to
+/// additionally reject non-canonical DAG-CBOR, or [`references_slice`] to
+/// avoid a `Vec` allocation per link when the block is already a `&[u8]`.
+pub fn references<R: Reader>(
+    r: &mut R,
+    res: &mut Vec<(u64, Hash)>,
+    limits: Limits,
+) -> Result<(), ParseError> {
+    references_impl(r, res, Strictness::Lenient, limits, read_link)
+}
 
-        // Major type 2: a byte string
-        0x40..=0x5b => {
-            let len = read_len(r, major - 0x40)?;
-            r.seek(SeekFrom::Current(len as _))?;
+/// Read a dag-cbor block and extract all the links, rejecting anything that
+/// is not canonical DAG-CBOR.
+///
+/// This is the check to use before trusting that a block actually hashes to
+/// its claimed CID: non-canonical encodings (indefinite lengths, non-minimal
+/// integers, non-canonical map key order, short floats, non-42 tags) are
+/// rejected instead of silently accepted.
+pub fn references_strict<R: Reader>(
+    r: &mut R,
+    res: &mut Vec<(u64, Hash)>,
+    limits: Limits,
+) -> Result<(), ParseError> {
+    references_impl(r, res, Strictness::Strict, limits, read_link)
+}
+
+/// Read a dag-cbor block and extract all the links as generic, multihash-aware
+/// [`Cid`]s, instead of requiring every link to be a blake3 hash.
+///
+/// Useful for indexing DAGs that mix hash algorithms (sha2-256, a truncated
+/// blake3, etc). Prefer [`references`] when every link is known to be
+/// blake3: it avoids one `Vec<u8>` allocation per link.
+pub fn references_generic<R: Reader>(
+    r: &mut R,
+    res: &mut Vec<Cid>,
+    strictness: Strictness,
+    limits: Limits,
+) -> Result<(), ParseError> {
+    references_impl(r, res, strictness, limits, read_cid)
+}
+
+/// Read a dag-cbor block already held in memory and extract all the links,
+/// like [`references`] but without allocating a `Vec` per link: CID digests
+/// are copied directly out of `data` via [`ByteCursor`]/[`SliceReader`]
+/// instead of through [`read_bytes`]'s intermediate buffer, and there is no
+/// `Read + Seek` seek churn since `data` is already fully in memory.
+///
+/// Prefer this over [`references`] whenever the whole block is already a
+/// `&[u8]`, e.g. after reading it from a blockstore.
+pub fn references_slice(
+    data: &[u8],
+    res: &mut Vec<(u64, Hash)>,
+    limits: Limits,
+) -> Result<(), ParseError> {
+    references_impl(
+        &mut ByteCursor::new(data),
+        res,
+        Strictness::Lenient,
+        limits,
+        read_link_slice,
+    )
+}
+
+/// Computes `2.0_f64.powi(n)` by constructing the IEEE-754 bit pattern
+/// directly, since `f64::powi` lives in `std` (it needs `libm`) and is
+/// unavailable in a `no_std` build; `n` stays well within the exponent
+/// range here (half-floats only need -24..=15).
+fn exp2(n: i32) -> f64 {
+    f64::from_bits(((n + 1023) as u64) << 52)
+}
+
+/// Turns a 16 bit half-float into a 64 bit float.
+///
+/// dag-cbor blocks should never legally contain these (see the strictness
+/// checks added for canonical validation), but `decode` still has to make
+/// sense of them when reading blocks produced by lenient encoders.
+fn f16_to_f64(half: u16) -> f64 {
+    let sign = (half >> 15) & 1;
+    let exp = (half >> 10) & 0x1f;
+    let frac = half & 0x3ff;
+
+    let value = if exp == 0 {
+        (frac as f64) * exp2(-24)
+    } else if exp == 0x1f {
+        if frac == 0 {
+            f64::INFINITY
+        } else {
+            f64::NAN
         }
+    } else {
+        (1.0 + (frac as f64) / 1024.0) * exp2(exp as i32 - 15)
+    };
+
+    if sign == 1 {
+        -value
+    } else {
+        value
+    }
+}
+
+/// A still-open array or map in [`decode_iter`]'s work stack, holding the
+/// values collected so far, analogous to [`Frame`] but carrying the partial
+/// [`Ipld`] container instead of just a remaining count.
+enum DecodeFrame {
+    /// A definite-length array; `remaining` more elements are expected.
+    Array { items: Vec<Ipld>, remaining: usize },
+    /// An indefinite-length array, terminated by a break byte.
+    IndefiniteArray { items: Vec<Ipld> },
+    /// A definite-length map; `remaining` more pairs are expected. `key`
+    /// holds the key once read, while its value is still being decoded.
+    Map {
+        map: BTreeMap<String, Ipld>,
+        key: Option<String>,
+        remaining: usize,
+    },
+    /// An indefinite-length map, terminated by a break byte.
+    IndefiniteMap {
+        map: BTreeMap<String, Ipld>,
+        key: Option<String>,
+    },
+}
+
+/// Pushes a container frame, enforcing `limits.max_depth`.
+fn push_decode_frame(
+    stack: &mut Vec<DecodeFrame>,
+    frame: DecodeFrame,
+    limits: &Limits,
+) -> Result<(), ParseError> {
+    if stack.len() >= limits.max_depth {
+        return Err(ParseError::DepthExceeded);
+    }
+    stack.push(frame);
+    Ok(())
+}
 
-        // Major type 3: a text string
-        0x60..=0x7b => {
-            let len = read_len(r, major - 0x60)?;
-            r.seek(SeekFrom::Current(len as _))?;
+/// Feeds a just-decoded `value` into the frame on top of `stack`, cascading
+/// into parent frames as they complete; mirrors [`finish_item`], but since
+/// each frame here owns a partial [`Ipld`] container, a completed frame
+/// turns into the next `value` to feed into *its* parent instead of just
+/// popping. Returns the fully assembled value once `stack` runs out.
+fn complete_value(stack: &mut Vec<DecodeFrame>, mut value: Ipld) -> Result<Option<Ipld>, ParseError> {
+    loop {
+        match stack.last_mut() {
+            None => return Ok(Some(value)),
+            Some(DecodeFrame::Array { items, remaining }) => {
+                items.push(value);
+                *remaining -= 1;
+                if *remaining > 0 {
+                    return Ok(None);
+                }
+                value = match stack.pop() {
+                    Some(DecodeFrame::Array { items, .. }) => Ipld::List(items),
+                    _ => unreachable!(),
+                };
+            }
+            Some(DecodeFrame::IndefiniteArray { items }) => {
+                items.push(value);
+                return Ok(None);
+            }
+            Some(DecodeFrame::Map { map, key, remaining }) => match key.take() {
+                None => {
+                    *key = Some(match value {
+                        Ipld::String(key) => key,
+                        _ => return Err(ParseError::InvalidMapKey),
+                    });
+                    return Ok(None);
+                }
+                Some(key) => {
+                    map.insert(key, value);
+                    *remaining -= 1;
+                    if *remaining > 0 {
+                        return Ok(None);
+                    }
+                    value = match stack.pop() {
+                        Some(DecodeFrame::Map { map, .. }) => Ipld::Map(map),
+                        _ => unreachable!(),
+                    };
+                }
+            },
+            Some(DecodeFrame::IndefiniteMap { map, key }) => match key.take() {
+                None => {
+                    *key = Some(match value {
+                        Ipld::String(key) => key,
+                        _ => return Err(ParseError::InvalidMapKey),
+                    });
+                    return Ok(None);
+                }
+                Some(key) => {
+                    map.insert(key, value);
+                    return Ok(None);
+                }
+            },
         }
+    }
+}
 
-        // Major type 4: an array of data items
-        0x80..=0x9b => {
-            let len = read_len(r, major - 0x80)?;
-            for _ in 0..len {
-                references(r, res)?;
+/// Shared iterative traversal behind [`decode`], built the same way
+/// [`references_impl`] is: an explicit work stack of open array/map frames
+/// instead of recursion, so a deeply nested block cannot blow the stack;
+/// `limits.max_depth` bounds the nesting.
+fn decode_iter<R: Reader>(r: &mut R, limits: &Limits) -> Result<Ipld, ParseError> {
+    let mut stack: Vec<DecodeFrame> = Vec::new();
+    loop {
+        if let Some(frame) = stack.last() {
+            if matches!(
+                frame,
+                DecodeFrame::IndefiniteArray { .. } | DecodeFrame::IndefiniteMap { .. }
+            ) {
+                let b = read_u8(r)?;
+                if b == 0xff {
+                    let completed = match stack.pop() {
+                        Some(DecodeFrame::IndefiniteArray { items }) => Ipld::List(items),
+                        Some(DecodeFrame::IndefiniteMap { map, key }) => {
+                            if key.is_some() {
+                                return Err(ParseError::InvalidMapKey);
+                            }
+                            Ipld::Map(map)
+                        }
+                        _ => unreachable!(),
+                    };
+                    if let Some(done) = complete_value(&mut stack, completed)? {
+                        return Ok(done);
+                    }
+                    continue;
+                }
+                r.seek_relative(-1)?;
             }
         }
 
-        // Major type 4: an array of data items (indefinite length)
-        0x9f => loop {
-            let major = read_u8(r)?;
-            if major == 0xff {
-                break;
+        let major = read_u8(r)?;
+        let value = match major {
+            // Major type 0: an unsigned integer
+            0x00..=0x17 => Ipld::Integer(major as i128),
+            0x18 => Ipld::Integer(read_u8(r)? as i128),
+            0x19 => Ipld::Integer(read_u16(r)? as i128),
+            0x1a => Ipld::Integer(read_u32(r)? as i128),
+            0x1b => Ipld::Integer(read_u64(r)? as i128),
+
+            // Major type 1: a negative integer
+            0x20..=0x37 => Ipld::Integer(-1 - (major - 0x20) as i128),
+            0x38 => Ipld::Integer(-1 - read_u8(r)? as i128),
+            0x39 => Ipld::Integer(-1 - read_u16(r)? as i128),
+            0x3a => Ipld::Integer(-1 - read_u32(r)? as i128),
+            0x3b => Ipld::Integer(-1 - read_u64(r)? as i128),
+
+            // Major type 2: a byte string
+            0x40..=0x5b => {
+                let len = read_len(r, major - 0x40, Strictness::Lenient)?;
+                Ipld::Bytes(read_bytes(r, len)?)
             }
-            r.seek(SeekFrom::Current(-1))?;
-            references(r, res)?;
-        },
 
-        // Major type 5: a map of pairs of data items
-        0xa0..=0xbb => {
-            let len = read_len(r, major - 0xa0)?;
-            for _ in 0..len {
-                references(r, res)?;
-                references(r, res)?;
+            // Major type 3: a text string
+            0x60..=0x7b => {
+                let len = read_len(r, major - 0x60, Strictness::Lenient)?;
+                let bytes = read_bytes(r, len)?;
+                Ipld::String(String::from_utf8(bytes).map_err(|_| ParseError::InvalidUtf8)?)
             }
-        }
 
-        // Major type 5: a map of pairs of data items (indefinite length)
-        0xbf => loop {
-            let major = read_u8(r)?;
-            if major == 0xff {
-                break;
-            }
-            r.seek(SeekFrom::Current(-1))?;
-            references(r, res)?;
-            references(r, res)?;
-        },
-
-        // Major type 6: optional semantic tagging of other major types
-        0xd8 => {
-            let tag = read_u8(r)?;
-            if tag == 42 {
-                res.push(read_link(r)?);
-            } else {
-                references(r, res)?;
+            // Major type 4: an array of data items
+            0x80..=0x9b => {
+                let len = read_len(r, major - 0x80, Strictness::Lenient)?;
+                if len == 0 {
+                    Ipld::List(Vec::new())
+                } else {
+                    push_decode_frame(
+                        &mut stack,
+                        DecodeFrame::Array {
+                            items: Vec::with_capacity(len.min(4096)),
+                            remaining: len,
+                        },
+                        limits,
+                    )?;
+                    continue;
+                }
             }
-        }
 
-        // Major type 7: floating-point numbers and other simple data types that need no content
-        0xf4..=0xf7 => {}
-        0xf8 => {
-            r.seek(SeekFrom::Current(1))?;
-        }
-        0xf9 => {
-            r.seek(SeekFrom::Current(2))?;
-        }
-        0xfa => {
-            r.seek(SeekFrom::Current(4))?;
-        }
-        0xfb => {
-            r.seek(SeekFrom::Current(8))?;
+            // Major type 4: an array of data items (indefinite length)
+            0x9f => {
+                push_decode_frame(&mut stack, DecodeFrame::IndefiniteArray { items: Vec::new() }, limits)?;
+                continue;
+            }
+
+            // Major type 5: a map of pairs of data items
+            0xa0..=0xbb => {
+                let len = read_len(r, major - 0xa0, Strictness::Lenient)?;
+                if len == 0 {
+                    Ipld::Map(BTreeMap::new())
+                } else {
+                    push_decode_frame(
+                        &mut stack,
+                        DecodeFrame::Map {
+                            map: BTreeMap::new(),
+                            key: None,
+                            remaining: len,
+                        },
+                        limits,
+                    )?;
+                    continue;
+                }
+            }
+
+            // Major type 5: a map of pairs of data items (indefinite length)
+            0xbf => {
+                push_decode_frame(
+                    &mut stack,
+                    DecodeFrame::IndefiniteMap {
+                        map: BTreeMap::new(),
+                        key: None,
+                    },
+                    limits,
+                )?;
+                continue;
+            }
+
+            // Major type 6: optional semantic tagging of other major types
+            0xd8 => {
+                let tag = read_u8(r)?;
+                if tag == 42 {
+                    let (codec, hash) = read_link(r)?;
+                    Ipld::Link(codec, hash)
+                } else {
+                    // the tag is transparent: loop around to read the value it wraps
+                    continue;
+                }
+            }
+
+            // Major type 7: floating-point numbers and other simple data types
+            0xf4 => Ipld::Bool(false),
+            0xf5 => Ipld::Bool(true),
+            0xf6 | 0xf7 => Ipld::Null,
+            0xf8 => {
+                r.seek_relative(1)?;
+                Ipld::Null
+            }
+            0xf9 => Ipld::Float(f16_to_f64(read_u16(r)?)),
+            0xfa => Ipld::Float(f32::from_bits(read_u32(r)?) as f64),
+            0xfb => Ipld::Float(f64::from_bits(read_u64(r)?)),
+            major => return Err(ParseError::UnexpectedCode(major)),
+        };
+
+        if let Some(done) = complete_value(&mut stack, value)? {
+            return Ok(done);
         }
-        major => return Err(ParseError::UnexpectedCode(major)),
-    };
-    Ok(())
+    }
+}
+
+/// Read a dag-cbor block and decode it into an owned [`Ipld`] tree.
+///
+/// Unlike [`references`], this keeps every decoded value around instead of
+/// discarding everything that isn't a link, so callers can inspect the
+/// actual contents of a block (e.g. read a `version` or `metadata` field).
+/// `limits.max_depth` bounds the nesting depth, same as [`references`];
+/// pass [`Limits::UNLIMITED`] to restore the crate's previous unbounded
+/// behavior.
+///
+/// Will fail unless all links are blake3 hashes, same as `references`.
+pub fn decode<R: Reader>(r: &mut R, limits: Limits) -> Result<Ipld, ParseError> {
+    decode_iter(r, &limits)
 }
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "std")]
     use std::io::Cursor;
 
-    use super::references;
+    use super::{references, ByteCursor, Limits};
+    #[cfg(feature = "std")]
+    use super::{decode, references_generic, references_slice, references_strict, Cid, Ipld, ParseError, Strictness};
+    #[cfg(not(feature = "std"))]
+    use alloc::{string::String, vec::Vec};
 
     fn bytes(s: &str) -> Vec<u8> {
         hex::decode(s.chars().filter(|c| !c.is_whitespace()).collect::<String>()).unwrap()
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn references1() {
         let data = vec![
             bytes(
@@ -328,8 +1169,310 @@ mod tests {
         ];
         for data in data {
             let mut links = Vec::new();
-            references(&mut Cursor::new(&data), &mut links).unwrap();
+            references(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED).unwrap();
             println!("{:?}", links);
         }
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn decode_map() {
+        let data = bytes(
+            r"
+            a564747970656c776e66732f7075622f6469726776657273696f6e65302e
+            322e30686d65746164617461a267637265617465641a643eddeb686d6f64
+            69666965641a643eddeb6870726576696f757381d82a58250001711e2045
+            c910e86e64f78a99dde9232e5978de40823eaa42732ff7a3814983d6969e
+            7368757365726c616e64a16474657374d82a58250001711e2082a8fc238c
+            9a05e2351f8ceaa4e5af2cdb39a895f6e929827a2614e61239d47c",
+        );
+        let ipld = decode(&mut Cursor::new(&data), Limits::UNLIMITED).unwrap();
+        match ipld {
+            Ipld::Map(map) => {
+                assert_eq!(map.get("type"), Some(&Ipld::String("wnfs/pub/dir".into())));
+                assert!(matches!(map.get("version"), Some(Ipld::String(_))));
+            }
+            other => panic!("expected a map, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn decode_rejects_invalid_utf8() {
+        // a text string of length 1 containing the invalid utf8 byte 0xff
+        let data = bytes("61ff");
+        assert!(matches!(
+            decode(&mut Cursor::new(&data), Limits::UNLIMITED),
+            Err(ParseError::InvalidUtf8)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn strict_rejects_non_minimal_length() {
+        // an array of one item, with its length encoded in the non-minimal
+        // 0x18 form (1 fits in the 0x00..=0x17 direct encoding)
+        let data = bytes("980100");
+        let mut links = Vec::new();
+        references(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED).unwrap();
+        assert!(matches!(
+            references_strict(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED),
+            Err(ParseError::NonMinimalInt)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn strict_rejects_indefinite_length() {
+        // an indefinite-length array containing a single 0: [0]
+        let data = bytes("9f00ff");
+        let mut links = Vec::new();
+        references(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED).unwrap();
+        assert!(matches!(
+            references_strict(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED),
+            Err(ParseError::IndefiniteLength)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn strict_rejects_out_of_order_map_keys() {
+        // {"b": 0, "a": 0}, keys encoded out of canonical order
+        let data = bytes("a2616200616100");
+        let mut links = Vec::new();
+        references(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED).unwrap();
+        assert!(matches!(
+            references_strict(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED),
+            Err(ParseError::NonCanonicalMapKey)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn strict_rejects_duplicate_map_keys() {
+        // {"a": 0, "a": 1}
+        let data = bytes("a2616100616101");
+        let mut links = Vec::new();
+        references(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED).unwrap();
+        assert!(matches!(
+            references_strict(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED),
+            Err(ParseError::NonCanonicalMapKey)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn strict_rejects_16_and_32_bit_floats() {
+        let half = bytes("f93c00"); // 1.0 as a 16 bit float
+        let single = bytes("fa3f800000"); // 1.0 as a 32 bit float
+        let mut links = Vec::new();
+        for data in [&half, &single] {
+            references(&mut Cursor::new(data), &mut links, Limits::UNLIMITED).unwrap();
+            assert!(matches!(
+                references_strict(&mut Cursor::new(data), &mut links, Limits::UNLIMITED),
+                Err(ParseError::NonCanonicalFloat)
+            ));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn strict_rejects_non_42_tags() {
+        // tag 1 wrapping the integer 0
+        let data = bytes("d80100");
+        let mut links = Vec::new();
+        references(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED).unwrap();
+        assert!(matches!(
+            references_strict(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED),
+            Err(ParseError::UnknownTag(1))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn too_many_links() {
+        let data = bytes(
+            r"
+            a564747970656c776e66732f7075622f6469726776657273696f6e65302e
+            322e30686d65746164617461a267637265617465641a643eddeb686d6f64
+            69666965641a643eddeb6870726576696f757381d82a58250001711e2045
+            c910e86e64f78a99dde9232e5978de40823eaa42732ff7a3814983d6969e
+            7368757365726c616e64a16474657374d82a58250001711e2082a8fc238c
+            9a05e2351f8ceaa4e5af2cdb39a895f6e929827a2614e61239d47c",
+        );
+        let mut links = Vec::new();
+        references(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED).unwrap();
+        assert_eq!(links.len(), 2);
+
+        links.clear();
+        assert!(matches!(
+            references(
+                &mut Cursor::new(&data),
+                &mut links,
+                Limits {
+                    max_links: 1,
+                    ..Limits::UNLIMITED
+                },
+            ),
+            Err(ParseError::TooManyLinks)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn huge_map_length_does_not_overflow() {
+        // a map with its pair count encoded as u64::MAX in the 8-byte form
+        let data = bytes("bbffffffffffffffff");
+        let mut links = Vec::new();
+        assert!(matches!(
+            references(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED),
+            Err(ParseError::LengthOutOfRange)
+        ));
+        assert!(matches!(
+            references_strict(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED),
+            Err(ParseError::LengthOutOfRange)
+        ));
+        let mut cids: Vec<Cid> = Vec::new();
+        assert!(matches!(
+            references_generic(
+                &mut Cursor::new(&data),
+                &mut cids,
+                Strictness::Lenient,
+                Limits::UNLIMITED,
+            ),
+            Err(ParseError::LengthOutOfRange)
+        ));
+        let mut via_slice = Vec::new();
+        assert!(matches!(
+            references_slice(&data, &mut via_slice, Limits::UNLIMITED),
+            Err(ParseError::LengthOutOfRange)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn depth_limit() {
+        // 4 singly-nested arrays: [[[[0]]]]
+        let data = bytes("8181818100");
+        let mut links = Vec::new();
+        references(&mut Cursor::new(&data), &mut links, Limits::UNLIMITED).unwrap();
+        assert!(matches!(
+            references(
+                &mut Cursor::new(&data),
+                &mut links,
+                Limits {
+                    max_depth: 2,
+                    ..Limits::UNLIMITED
+                },
+            ),
+            Err(ParseError::DepthExceeded)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn decode_depth_limit() {
+        // 4 singly-nested arrays: [[[[0]]]]
+        let data = bytes("8181818100");
+        decode(&mut Cursor::new(&data), Limits::UNLIMITED).unwrap();
+        assert!(matches!(
+            decode(
+                &mut Cursor::new(&data),
+                Limits {
+                    max_depth: 2,
+                    ..Limits::UNLIMITED
+                },
+            ),
+            Err(ParseError::DepthExceeded)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn decode_rejects_a_much_deeper_bomb_without_overflowing_the_stack() {
+        // 200_000 singly-nested arrays: [[[...[0]...]]], matching the
+        // stack-overflow repro that prompted this fix; a bounded max_depth
+        // catches it with a plain error walking the stack iteratively
+        // instead of recursing (and, crucially, instead of ever
+        // materializing a 200_000-deep `Ipld::List` chain, which would
+        // itself overflow the stack again on drop).
+        let mut data = vec![0x81u8; 200_000];
+        data.push(0x00);
+        assert!(matches!(
+            decode(
+                &mut Cursor::new(&data),
+                Limits {
+                    max_depth: 1024,
+                    ..Limits::UNLIMITED
+                },
+            ),
+            Err(ParseError::DepthExceeded)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn references_generic_decodes_blake3_as_cid() {
+        let data = bytes(
+            r"
+            a564747970656c776e66732f7075622f6469726776657273696f6e65302e
+            322e30686d65746164617461a267637265617465641a643eddeb686d6f64
+            69666965641a643eddeb6870726576696f757381d82a58250001711e2045
+            c910e86e64f78a99dde9232e5978de40823eaa42732ff7a3814983d6969e
+            7368757365726c616e64a16474657374d82a58250001711e2082a8fc238c
+            9a05e2351f8ceaa4e5af2cdb39a895f6e929827a2614e61239d47c",
+        );
+        let mut cids: Vec<Cid> = Vec::new();
+        references_generic(
+            &mut Cursor::new(&data),
+            &mut cids,
+            Strictness::Lenient,
+            Limits::UNLIMITED,
+        )
+        .unwrap();
+        assert_eq!(cids.len(), 2);
+        for cid in &cids {
+            assert_eq!(cid.hash_code, 0x1e);
+            assert_eq!(cid.digest.len(), 32);
+        }
+    }
+
+    #[test]
+    fn references_over_byte_cursor() {
+        // same fixture as `references1`, but read via the no_std-friendly
+        // `ByteCursor` instead of `std::io::Cursor`.
+        let data = bytes(
+            r"
+            a564747970656c776e66732f7075622f6469726776657273696f6e65302e
+            322e30686d65746164617461a267637265617465641a643eddeb686d6f64
+            69666965641a643eddeb6870726576696f757381d82a58250001711e2045
+            c910e86e64f78a99dde9232e5978de40823eaa42732ff7a3814983d6969e
+            7368757365726c616e64a16474657374d82a58250001711e2082a8fc238c
+            9a05e2351f8ceaa4e5af2cdb39a895f6e929827a2614e61239d47c",
+        );
+        let mut links = Vec::new();
+        references(&mut ByteCursor::new(&data), &mut links, Limits::UNLIMITED).unwrap();
+        assert_eq!(links.len(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn references_slice_matches_references() {
+        let data = bytes(
+            r"
+            a564747970656c776e66732f7075622f6469726776657273696f6e65302e
+            322e30686d65746164617461a267637265617465641a643eddeb686d6f64
+            69666965641a643eddeb6870726576696f757381d82a58250001711e2045
+            c910e86e64f78a99dde9232e5978de40823eaa42732ff7a3814983d6969e
+            7368757365726c616e64a16474657374d82a58250001711e2082a8fc238c
+            9a05e2351f8ceaa4e5af2cdb39a895f6e929827a2614e61239d47c",
+        );
+        let mut via_reader = Vec::new();
+        references(&mut Cursor::new(&data), &mut via_reader, Limits::UNLIMITED).unwrap();
+
+        let mut via_slice = Vec::new();
+        references_slice(&data, &mut via_slice, Limits::UNLIMITED).unwrap();
+
+        assert_eq!(via_reader, via_slice);
+    }
 }